@@ -1,28 +1,70 @@
 use anyhow::Result;
+use async_graphql::{
+    dataloader::{DataLoader, Loader},
+    http::{playground_source, GraphQLPlaygroundConfig},
+    Context, EmptyMutation, EmptySubscription, Object, Schema, SimpleObject,
+};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
 use axum::{
-    extract::{Path, State},
-    response::IntoResponse,
+    extract::{Form, Multipart, Path, State},
+    response::{Html, IntoResponse, Response},
     routing::{get, post},
-    Json, Router,
+    Extension, Json, Router,
+};
+use http::{
+    header::{ACCEPT, CONTENT_TYPE, LOCATION},
+    HeaderMap, HeaderValue, StatusCode,
 };
-use http::{header::LOCATION, HeaderMap, StatusCode};
 use nanoid::nanoid;
 use serde::{Deserialize, Serialize};
+use sqids::Sqids;
 use sqlx::{migrate::MigrateDatabase, FromRow, Sqlite, SqlitePool};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use tokio::net::TcpListener;
 use tracing::{info, level_filters::LevelFilter, warn};
 use tracing_subscriber::{fmt::Layer, layer::SubscriberExt, util::SubscriberInitExt, Layer as _};
+use url::Url;
+
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// 允许原样回放的paste内容类型：纯文本、不透明二进制和光栅图片格式。
+/// 刻意不包含`image/svg+xml`——SVG是可以内嵌`<script>`的XML，会被浏览器当作HTML文档执行。
+const ALLOWED_PASTE_CONTENT_TYPES: &[&str] = &[
+    "text/plain",
+    "application/octet-stream",
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+];
 
 const LISTEN_ADDR: &str = "127.0.0.1:8080";
+const SQIDS_ALPHABET: &str = "FxnXM1kBN6cuhsAvjW2RTyE7Lg0HIod3DeYCrUi4zKb5OPlJtmQf8wGZV9pSqa";
+const SQIDS_MIN_LENGTH: u8 = 6;
 
 #[derive(Debug, Clone)]
 struct AppState {
     db: SqlitePool,
+    sqids: Sqids,
+    health: Arc<Mutex<HealthStatus>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct HealthStatus {
+    healthy: bool,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 struct ShortenReq {
     url: String,
+    #[serde(default)]
+    ttl_seconds: Option<i64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -30,14 +72,145 @@ struct ShortenRes {
     url: String,
 }
 
+#[derive(Debug, Serialize)]
+struct StatsRes {
+    url: String,
+    clicks: i64,
+    created_at: String,
+    expires_at: Option<String>,
+}
+
 #[derive(Debug, FromRow)]
 struct UrlRecord {
     #[sqlx(default)]
-    id: String,
+    id: i64,
+    #[sqlx(default)]
+    url: String,
+    #[sqlx(default)]
+    clicks: i64,
+    #[sqlx(default)]
+    created_at: String,
+    #[sqlx(default)]
+    expires_at: Option<String>,
     #[sqlx(default)]
+    expired: bool,
+}
+
+enum LinkStatus {
+    Active(String),
+    Expired,
+}
+
+#[derive(Debug, Serialize)]
+struct PasteRes {
     url: String,
 }
 
+#[derive(Debug, FromRow)]
+struct PasteRecord {
+    #[sqlx(default)]
+    content: Vec<u8>,
+    #[sqlx(default)]
+    content_type: String,
+}
+
+type LinkSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// 只读GraphQL视图，字段语义与REST的stats/shorten保持一致
+#[derive(Debug, Clone, SimpleObject)]
+struct LinkGQL {
+    id: String,
+    url: String,
+    clicks: i64,
+    created_at: String,
+    expires_at: Option<String>,
+}
+
+struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// 按短码查询单个链接，借助DataLoader与同一次请求中的其它`link`字段合并为一次批量查询
+    async fn link(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<Option<LinkGQL>> {
+        let loader = ctx.data::<DataLoader<UrlLoader>>()?;
+        Ok(loader.load_one(id).await?)
+    }
+
+    /// 按行id游标分页列出链接
+    async fn links(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i64>,
+        after: Option<String>,
+    ) -> async_graphql::Result<Vec<LinkGQL>> {
+        let state = ctx.data::<AppState>()?;
+        let limit = first.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+        let after_row_id = after
+            .as_deref()
+            .map(|code| state.sqids.decode(code))
+            .and_then(|numbers| numbers.first().copied())
+            .unwrap_or(0);
+
+        let rows: Vec<UrlRecord> = sqlx::query_as(
+            "SELECT id, url, clicks, created_at, expires_at FROM short_urls
+             WHERE id > $1 ORDER BY id LIMIT $2",
+        )
+        .bind(after_row_id as i64)
+        .bind(limit)
+        .fetch_all(&state.db)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| state.to_link_gql(row))
+            .collect())
+    }
+}
+
+/// 按短码批量加载链接，将同一次GraphQL请求中的多个`link(id)`合并为一次`IN`查询
+struct UrlLoader {
+    state: AppState,
+}
+
+impl Loader<String> for UrlLoader {
+    type Value = LinkGQL;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(&self, ids: &[String]) -> Result<HashMap<String, Self::Value>, Self::Error> {
+        let mut code_by_row_id = HashMap::new();
+        for id in ids {
+            if let [row_id] = self.state.sqids.decode(id)[..] {
+                code_by_row_id.insert(row_id as i64, id.clone());
+            }
+        }
+        if code_by_row_id.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let placeholders = (1..=code_by_row_id.len())
+            .map(|i| format!("${i}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
+            "SELECT id, url, clicks, created_at, expires_at FROM short_urls WHERE id IN ({placeholders})"
+        );
+        let mut query = sqlx::query_as::<_, UrlRecord>(&sql);
+        for row_id in code_by_row_id.keys() {
+            query = query.bind(row_id);
+        }
+        let rows = query.fetch_all(&self.state.db).await.map_err(Arc::new)?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                code_by_row_id
+                    .get(&row.id)
+                    .map(|code| (code.clone(), self.state.to_link_gql(row)))
+            })
+            .collect())
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let layer = Layer::new().with_filter(LevelFilter::INFO);
@@ -49,12 +222,38 @@ async fn main() -> Result<()> {
     let app_state = AppState::try_new(db_url).await?;
     info!("connect to database successfully");
 
+    let monitor_state = app_state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            monitor_state.check_health().await;
+        }
+    });
+
+    let loader = DataLoader::new(
+        UrlLoader {
+            state: app_state.clone(),
+        },
+        tokio::spawn,
+    );
+    let schema = Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(app_state.clone())
+        .data(loader)
+        .finish();
+
     let listener = TcpListener::bind(LISTEN_ADDR).await?;
     info!("listening on {}", LISTEN_ADDR);
 
     let app = Router::new()
         .route("/:id", get(redirect))
+        .route("/:id/stats", get(stats))
         .route("/", post(shorten))
+        .route("/shorten", post(shorten_form))
+        .route("/paste", post(paste))
+        .route("/health", get(health))
+        .route("/graphql", get(graphql_playground).post(graphql_handler))
+        .layer(Extension(schema))
         .with_state(app_state);
 
     axum::serve(listener, app.into_make_service()).await?;
@@ -65,31 +264,186 @@ async fn main() -> Result<()> {
 async fn redirect(
     Path(id): Path<String>,
     State(app_state): State<AppState>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let url = app_state
-        .get_url(&id)
+) -> Result<Response, StatusCode> {
+    match app_state.hit(&id).await {
+        Ok(LinkStatus::Active(url)) => {
+            let location = url.parse().map_err(|_| {
+                warn!("Stored URL for {id} is not a valid header value: {url:?}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+            let mut headers = HeaderMap::new();
+            headers.insert(LOCATION, location);
+            return Ok((StatusCode::PERMANENT_REDIRECT, headers).into_response());
+        }
+        Ok(LinkStatus::Expired) => return Ok(StatusCode::GONE.into_response()),
+        Err(e) if matches!(e.downcast_ref::<sqlx::Error>(), Some(sqlx::Error::RowNotFound)) => {}
+        Err(e) => {
+            warn!("Failed to look up short link {id}: {e}");
+            return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    }
+
+    let paste = app_state
+        .get_paste(&id)
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
     let mut headers = HeaderMap::new();
-    headers.insert(LOCATION, url.parse().unwrap());
-    Ok((StatusCode::PERMANENT_REDIRECT, headers))
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_str(&paste.content_type)
+            .unwrap_or_else(|_| HeaderValue::from_static("application/octet-stream")),
+    );
+    Ok((StatusCode::OK, headers, paste.content).into_response())
+}
+
+/// 返回短链的创建时间、点击次数和目标url
+async fn stats(
+    Path(id): Path<String>,
+    State(app_state): State<AppState>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let row = app_state
+        .get_stats(&id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    Ok(Json(StatsRes {
+        url: row.url,
+        clicks: row.clicks,
+        created_at: row.created_at,
+        expires_at: row.expires_at,
+    }))
+}
+
+/// GraphQL Playground，便于在浏览器中交互式探索schema
+async fn graphql_playground() -> impl IntoResponse {
+    Html(playground_source(GraphQLPlaygroundConfig::new("/graphql")))
+}
+
+async fn graphql_handler(
+    Extension(schema): Extension<LinkSchema>,
+    req: GraphQLRequest,
+) -> GraphQLResponse {
+    schema.execute(req.into_inner()).await.into()
+}
+
+/// 返回后台监控任务记录的最近一次健康状态，不重复探测数据库
+async fn health(State(app_state): State<AppState>) -> impl IntoResponse {
+    let status = app_state.cached_health();
+    let code = if status.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(status))
+}
+
+/// 是否允许原样回放该内容类型：仅限白名单中的纯文本/二进制/光栅图片类型，其余（如`text/html`、`image/svg+xml`）一律拒绝
+fn is_allowed_paste_content_type(content_type: &str) -> bool {
+    let base = content_type.split(';').next().unwrap_or("").trim();
+    ALLOWED_PASTE_CONTENT_TYPES.contains(&base)
+}
+
+/// 接收multipart上传，保存为文本/文件片段并返回其访问url；paste不设过期时间，不会被自动清理
+async fn paste(
+    State(app_state): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, StatusCode> {
+    let mut content: Option<Vec<u8>> = None;
+    let mut content_type = "application/octet-stream".to_string();
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        warn!("Failed to read multipart field: {e}");
+        StatusCode::UNPROCESSABLE_ENTITY
+    })? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        if let Some(ct) = field.content_type() {
+            content_type = ct.to_string();
+        }
+        content = Some(
+            field
+                .bytes()
+                .await
+                .map_err(|e| {
+                    warn!("Failed to read multipart body: {e}");
+                    StatusCode::UNPROCESSABLE_ENTITY
+                })?
+                .to_vec(),
+        );
+        break;
+    }
+    let content = content.ok_or(StatusCode::UNPROCESSABLE_ENTITY)?;
+    if !is_allowed_paste_content_type(&content_type) {
+        warn!("Rejected paste upload with disallowed content type: {content_type}");
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let id = app_state
+        .create_paste(content, &content_type)
+        .await
+        .map_err(|e| {
+            warn!("Failed to store paste: {e}");
+            StatusCode::UNPROCESSABLE_ENTITY
+        })?;
+    let body = Json(PasteRes {
+        url: format!("http://{}/{}", LISTEN_ADDR, id),
+    });
+    Ok((StatusCode::CREATED, body))
+}
+
+/// 校验req并调用AppState::shorten，供JSON/表单两个入口共用
+async fn validate_and_shorten(app_state: &AppState, req: &ShortenReq) -> Result<String, StatusCode> {
+    let parsed_url = Url::parse(&req.url).map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+    if req.ttl_seconds.is_some_and(|ttl| ttl < 0) {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    app_state
+        .shorten(parsed_url.as_str(), req.ttl_seconds)
+        .await
+        .map_err(|e| {
+            warn!("Failed to shorten URL: {e}");
+            StatusCode::UNPROCESSABLE_ENTITY
+        })
 }
 
 async fn shorten(
     State(app_state): State<AppState>,
     Json(req): Json<ShortenReq>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let id = app_state.shorten(&req.url).await.map_err(|e| {
-        warn!("Failed to shorten URL: {e}");
-        StatusCode::UNPROCESSABLE_ENTITY
-    })?;
+    let id = validate_and_shorten(&app_state, &req).await?;
     let body = Json(ShortenRes {
         url: format!("http://{}/{}", LISTEN_ADDR, id),
     });
     Ok((StatusCode::CREATED, body))
 }
 
+/// 供`<form>`提交使用，按Accept头返回HTML或JSON
+async fn shorten_form(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Form(req): Form<ShortenReq>,
+) -> Result<Response, StatusCode> {
+    let id = validate_and_shorten(&app_state, &req).await?;
+    let short_url = format!("http://{}/{}", LISTEN_ADDR, id);
+
+    let wants_html = headers
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+
+    if wants_html {
+        let body = Html(format!(
+            "<!DOCTYPE html><html><body><p>Your short link: <a href=\"{0}\">{0}</a></p></body></html>",
+            short_url
+        ));
+        Ok((StatusCode::CREATED, body).into_response())
+    } else {
+        Ok((StatusCode::CREATED, Json(ShortenRes { url: short_url })).into_response())
+    }
+}
+
 impl AppState {
     async fn try_new(url: &str) -> Result<Self> {
         // 如果数据库不存在，创建一个新的数据库
@@ -101,34 +455,138 @@ impl AppState {
         // 初始化数据库表
         sqlx::query(
             "CREATE TABLE IF NOT EXISTS short_urls (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                url TEXT NOT NULL UNIQUE,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                clicks INTEGER NOT NULL DEFAULT 0,
+                expires_at TIMESTAMP
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS pastes (
                 id TEXT PRIMARY KEY,
-                url TEXT NOT NULL UNIQUE
+                content BLOB NOT NULL,
+                content_type TEXT NOT NULL,
+                created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
             )",
         )
         .execute(&pool)
         .await?;
         info!("create schema successfully");
 
-        Ok(Self { db: pool })
+        let sqids = Sqids::builder()
+            .alphabet(SQIDS_ALPHABET.chars().collect())
+            .min_length(SQIDS_MIN_LENGTH)
+            .build()?;
+
+        let health = Arc::new(Mutex::new(HealthStatus { healthy: true }));
+
+        Ok(Self {
+            db: pool,
+            sqids,
+            health,
+        })
     }
 
-    /// id保存到数据库，并返回id
-    async fn shorten(&self, url: &str) -> Result<String> {
-        let id = nanoid!(6);
-        let row:UrlRecord= sqlx::query_as("INSERT INTO short_urls (id, url) VALUES ($1, $2) ON CONFLICT(url) DO UPDATE SET url=EXCLUDED.url RETURNING id")
-            .bind(&id)
-            .bind(url)
-            .fetch_one(&self.db)
-            .await?;
-        Ok(row.id)
+    /// 探测数据库连接池并记录最近一次健康状态，供后台监控任务周期调用；探测失败时记录告警日志
+    async fn check_health(&self) {
+        let healthy = sqlx::query("SELECT 1").execute(&self.db).await.is_ok();
+        if !healthy {
+            warn!("database health check failed: connection pool unreachable");
+        }
+        *self.health.lock().unwrap() = HealthStatus { healthy };
     }
 
-    /// 根据id查询url
-    async fn get_url(&self, id: &str) -> Result<String> {
-        let ret: UrlRecord = sqlx::query_as("SELECT url FROM short_urls WHERE id = $1")
+    /// 读取后台监控任务记录的最近一次健康状态，不触发新的探测
+    fn cached_health(&self) -> HealthStatus {
+        self.health.lock().unwrap().clone()
+    }
+
+    /// 保存url到数据库，返回Sqids编码后的短码；ttl_seconds非空时设置过期时间
+    async fn shorten(&self, url: &str, ttl_seconds: Option<i64>) -> Result<String> {
+        let row: UrlRecord = sqlx::query_as(
+            "INSERT INTO short_urls (url, expires_at) VALUES (
+                $1,
+                CASE WHEN $2 IS NOT NULL THEN datetime('now', '+' || $2 || ' seconds') ELSE NULL END
+            ) ON CONFLICT(url) DO UPDATE SET url=EXCLUDED.url, expires_at=EXCLUDED.expires_at RETURNING id",
+        )
+        .bind(url)
+        .bind(ttl_seconds)
+        .fetch_one(&self.db)
+        .await?;
+        Ok(self.sqids.encode(&[row.id as u64])?)
+    }
+
+    /// 根据短码原子地记录一次点击，并返回目标url或过期状态；解码失败则按字符串兼容旧短码
+    async fn hit(&self, id: &str) -> Result<LinkStatus> {
+        const HIT_SQL: &str = "UPDATE short_urls SET clicks = clicks + 1 WHERE id = $1
+            RETURNING url, expires_at, (expires_at IS NOT NULL AND expires_at < CURRENT_TIMESTAMP) AS expired";
+
+        let row: UrlRecord = if let [row_id] = self.sqids.decode(id)[..] {
+            sqlx::query_as(HIT_SQL)
+                .bind(row_id as i64)
+                .fetch_one(&self.db)
+                .await?
+        } else {
+            sqlx::query_as(HIT_SQL).bind(id).fetch_one(&self.db).await?
+        };
+
+        Ok(if row.expired {
+            LinkStatus::Expired
+        } else {
+            LinkStatus::Active(row.url)
+        })
+    }
+
+    /// 根据短码查询创建时间、点击次数与目标url；解码失败则按字符串兼容旧短码
+    async fn get_stats(&self, id: &str) -> Result<UrlRecord> {
+        const STATS_SQL: &str =
+            "SELECT url, clicks, created_at, expires_at FROM short_urls WHERE id = $1";
+
+        if let [row_id] = self.sqids.decode(id)[..] {
+            return Ok(sqlx::query_as(STATS_SQL)
+                .bind(row_id as i64)
+                .fetch_one(&self.db)
+                .await?);
+        }
+        Ok(sqlx::query_as(STATS_SQL)
             .bind(id)
             .fetch_one(&self.db)
+            .await?)
+    }
+
+    /// 保存上传内容到pastes表，返回其短码
+    async fn create_paste(&self, content: Vec<u8>, content_type: &str) -> Result<String> {
+        let id = nanoid!(8);
+        sqlx::query("INSERT INTO pastes (id, content, content_type) VALUES ($1, $2, $3)")
+            .bind(&id)
+            .bind(content)
+            .bind(content_type)
+            .execute(&self.db)
             .await?;
-        Ok(ret.url)
+        Ok(id)
+    }
+
+    /// 根据短码查询paste内容
+    async fn get_paste(&self, id: &str) -> Result<PasteRecord> {
+        let ret: PasteRecord =
+            sqlx::query_as("SELECT content, content_type FROM pastes WHERE id = $1")
+                .bind(id)
+                .fetch_one(&self.db)
+                .await?;
+        Ok(ret)
+    }
+
+    /// 将数据库行转换为GraphQL对外暴露的短码形式
+    fn to_link_gql(&self, row: UrlRecord) -> LinkGQL {
+        LinkGQL {
+            id: self.sqids.encode(&[row.id as u64]).unwrap_or_default(),
+            url: row.url,
+            clicks: row.clicks,
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+        }
     }
 }